@@ -1,12 +1,68 @@
 use std::str::FromStr;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::quote;
+use quote::{format_ident, quote};
 use syn::*;
 
 pub fn derive(item: TokenStream) -> TokenStream {
     let item = parse_macro_input!(item as DeriveInput);
 
+    match &item.data {
+        Data::Struct(_) => derive_struct(item),
+        Data::Enum(_) => derive_enum(item),
+        Data::Union(_) => panic!("Unions are not supported"),
+    }
+}
+
+/// A field's relationship to the composite struct's locking scheme.
+enum FieldKind<'a> {
+    /// An ordinary field, created/flushed independently like today.
+    Plain(&'a Type),
+    /// A field of type `SharedLock`, shared by every `Locked` field in the
+    /// struct. Carries no data of its own, so it has no `Self::Encoding`
+    /// slot and no substore.
+    Lock,
+    /// A field of type `Locked<Inner>`, registered under the struct's
+    /// `SharedLock` field via [`Locked::create_field`]/`flush_field`
+    /// instead of getting its own lock.
+    Locked(&'a Type),
+}
+
+fn field_kind(ty: &Type) -> FieldKind {
+    let segment = match ty {
+        Type::Path(TypePath { path, .. }) => path.segments.last(),
+        _ => None,
+    };
+    let segment = match segment {
+        Some(segment) => segment,
+        None => return FieldKind::Plain(ty),
+    };
+
+    if segment.ident == "SharedLock" {
+        return FieldKind::Lock;
+    }
+
+    if segment.ident == "Locked" {
+        if let PathArguments::AngleBracketed(args) = &segment.arguments {
+            if let Some(GenericArgument::Type(inner)) = args.args.first() {
+                return FieldKind::Locked(inner);
+            }
+        }
+    }
+
+    FieldKind::Plain(ty)
+}
+
+/// Derives `State` for a struct.
+///
+/// A field's type opts it into `SharedLock` grouping: a field of type
+/// `orga::store::shared_lock::SharedLock` is constructed fresh in `create`
+/// and cloned into `Self` (it carries no data of its own, so it gets no
+/// `Self::Encoding` slot or substore), and any field of type `Locked<Inner>`
+/// is created via `Locked::create_field`/flushed via `Locked::flush_field`
+/// against that one lock instead of getting its own. Every other field is
+/// created/flushed independently, as before.
+fn derive_struct(item: DeriveInput) -> TokenStream {
     let mut is_tuple_struct = false;
     match &item.data {
         Data::Struct(data) => match data.fields {
@@ -16,66 +72,107 @@ pub fn derive(item: TokenStream) -> TokenStream {
         _ => {},
     }
 
-    let field_names = || struct_fields(&item).map(|field| &field.ident);
-    let field_types = || struct_fields(&item).map(|field| &field.ty);
-    let seq = || {
-        (0..field_names().count())
-            .map(|i| TokenStream2::from_str(&i.to_string()).unwrap())
+    let name = &item.ident;
+    let fields: Vec<&Field> = struct_fields(&item).collect();
+
+    let field_access = |pos: usize| -> TokenStream2 {
+        match &fields[pos].ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = syn::Index::from(pos);
+                quote!(#index)
+            }
+        }
     };
 
-    let name = &item.ident;
-    let field_types_encoding = field_types();
-    let seq_substore = seq();
-    let seq_data = seq();
-    let field_names_flush = field_names();
-
-    let create_body = if is_tuple_struct {
-        quote!(
-            Ok(Self(
-                #(
-                    ::orga::state::State::create(
-                        store.sub(&[#seq_substore]),
-                        data.#seq_data,
-                    )?,
-                )*
-            ))
-        )
+    let lock_field_pos = fields.iter().position(|field| matches!(field_kind(&field.ty), FieldKind::Lock));
+    let lock_var = format_ident!("__shared_lock");
+    let guard_var = format_ident!("__shared_lock_guard");
+
+    // Only non-`SharedLock` fields get a `Self::Encoding` slot and a
+    // substore, numbered in struct field order.
+    let mut encoding_types = Vec::new();
+    let mut create_items = Vec::new();
+    let mut flush_items = Vec::new();
+    let mut state_index: u8 = 0;
+
+    for (pos, field) in fields.iter().enumerate() {
+        let access = field_access(pos);
+
+        match field_kind(&field.ty) {
+            FieldKind::Lock => {
+                create_items.push((access.clone(), quote!(#lock_var.clone())));
+            }
+            FieldKind::Locked(inner_ty) => {
+                if lock_field_pos.is_none() {
+                    panic!(
+                        "a `Locked` field requires the struct to also have a field of type `SharedLock`"
+                    );
+                }
+                let index = TokenStream2::from_str(&state_index.to_string()).unwrap();
+                encoding_types.push(quote!(<#inner_ty as ::orga::state::State>::Encoding));
+                create_items.push((
+                    access.clone(),
+                    quote!(
+                        ::orga::store::shared_lock::Locked::create_field(
+                            &#lock_var,
+                            store.sub(&[#index]),
+                            data.#index,
+                        )?
+                    ),
+                ));
+                flush_items.push(quote!(self.#access.flush_field(&#guard_var)?,));
+                state_index += 1;
+            }
+            FieldKind::Plain(ty) => {
+                let index = TokenStream2::from_str(&state_index.to_string()).unwrap();
+                encoding_types.push(quote!(<#ty as ::orga::state::State>::Encoding));
+                create_items.push((
+                    access.clone(),
+                    quote!(
+                        ::orga::state::State::create(
+                            store.sub(&[#index]),
+                            data.#index,
+                        )?
+                    ),
+                ));
+                flush_items.push(quote!(self.#access.flush()?,));
+                state_index += 1;
+            }
+        }
+    }
+
+    let build_self = if is_tuple_struct {
+        let exprs = create_items.iter().map(|(_, expr)| expr);
+        quote!(Self(#(#exprs,)*))
     } else {
-        let names = field_names();
-        quote!(
-            Ok(Self {
-                #(
-                    #names: ::orga::state::State::create(
-                        store.sub(&[#seq_substore]),
-                        data.#seq_data,
-                    )?,
-                )*
-            })
-        ) 
+        let items = create_items.iter().map(|(access, expr)| quote!(#access: #expr,));
+        quote!(Self { #(#items)* })
     };
 
-    let flush_body = if is_tuple_struct {
-        let indexes = seq();
-        quote!(
-            Ok((
-                #(self.#indexes.flush()?,)*
-            ))
-        )
-    } else {
-        let names = field_names();
-        quote!(
-            Ok((
-                #(self.#names.flush()?,)*
-            ))
-        )
+    let create_body = match lock_field_pos {
+        Some(_) => quote!(
+            let #lock_var = ::orga::store::shared_lock::SharedLock::new();
+            Ok(#build_self)
+        ),
+        None => quote!(Ok(#build_self)),
+    };
+
+    let flush_body = match lock_field_pos {
+        Some(pos) => {
+            let lock_access = field_access(pos);
+            quote!(
+                let #guard_var = self.#lock_access.write();
+                Ok((#(#flush_items)*))
+            )
+        }
+        None => quote!(Ok((#(#flush_items)*))),
     };
 
     let output = quote! {
         impl ::orga::state::State for #name {
             type Encoding = (
-                #(
-                    <#field_types_encoding as ::orga::state::State>::Encoding,
-                )*
+                #(#encoding_types,)*
             );
 
             fn create(
@@ -99,8 +196,7 @@ fn struct_fields<'a>(
 ) -> impl Iterator<Item=&'a Field> {
     let data = match item.data {
         Data::Struct(ref data) => data,
-        Data::Enum(ref _data) => todo!("#[derive(State)] does not yet support enums"),
-        Data::Union(_) => panic!("Unions are not supported"),
+        Data::Enum(_) | Data::Union(_) => unreachable!("only called for structs"),
     };
 
     match data.fields {
@@ -109,3 +205,177 @@ fn struct_fields<'a>(
         Fields::Unit => panic!("Unit structs are not supported"),
     }
 }
+
+/// Derives `State` for an enum by encoding the active variant as a leading
+/// discriminant (handled by the generated `Encoding` enum's own
+/// `Encode`/`Decode` impl) followed by that variant's fields, laid out in
+/// substores exactly as the struct derive does for its fields - each
+/// variant reuses the same local `0..N` substore indexes the struct derive
+/// would assign to an equivalent struct's fields.
+///
+/// Because different variants can reuse the same index for differently-
+/// typed fields, `create` also clears any trailing substore indexes that
+/// the newly-active variant doesn't use but a variant with more fields
+/// could have written to, so shrinking the field count on a variant switch
+/// doesn't leave that tail of old field data sitting around unreachable.
+///
+/// Known limitation: this only clears the top-level key of an unused
+/// trailing substore slot, not an entire substore subtree. A field type
+/// that itself fans out into many keys under its substore (e.g. a
+/// `Map`-backed variant field) isn't fully cleared by this when a sibling
+/// variant takes over the same index - that needs a "delete everything
+/// under this substore" primitive the store layer doesn't expose yet.
+fn derive_enum(item: DeriveInput) -> TokenStream {
+    let data = match &item.data {
+        Data::Enum(data) => data,
+        _ => unreachable!(),
+    };
+
+    let name = &item.ident;
+    let encoding_name = format_ident!("{}Encoding", name);
+
+    enum VariantFields<'a> {
+        Unit,
+        Unnamed(Vec<&'a Type>),
+        Named(Vec<&'a Ident>, Vec<&'a Type>),
+    }
+
+    let variants: Vec<(&Ident, VariantFields)> = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let fields = match &variant.fields {
+                Fields::Unit => VariantFields::Unit,
+                Fields::Unnamed(fields) => {
+                    VariantFields::Unnamed(fields.unnamed.iter().map(|f| &f.ty).collect())
+                }
+                Fields::Named(fields) => VariantFields::Named(
+                    fields.named.iter().map(|f| f.ident.as_ref().unwrap()).collect(),
+                    fields.named.iter().map(|f| &f.ty).collect(),
+                ),
+            };
+            (&variant.ident, fields)
+        })
+        .collect();
+
+    let max_fields = variants
+        .iter()
+        .map(|(_, fields)| match fields {
+            VariantFields::Unit => 0,
+            VariantFields::Unnamed(types) => types.len(),
+            VariantFields::Named(idents, _) => idents.len(),
+        })
+        .max()
+        .unwrap_or(0);
+
+    let seq = |n: usize| (0..n).map(|i| TokenStream2::from_str(&i.to_string()).unwrap());
+
+    let mut encoding_variants = Vec::new();
+    let mut create_arms = Vec::new();
+    let mut flush_arms = Vec::new();
+
+    for (variant_ident, fields) in variants.iter() {
+        let field_count = match fields {
+            VariantFields::Unit => 0,
+            VariantFields::Unnamed(types) => types.len(),
+            VariantFields::Named(idents, _) => idents.len(),
+        };
+        let stale_indexes: Vec<_> = seq(max_fields).skip(field_count).collect();
+        let clear_stale = quote!(
+            #(
+                ::orga::store::Write::delete(&mut store.sub(&[#stale_indexes]), &[])?;
+            )*
+        );
+
+        match fields {
+            VariantFields::Unit => {
+                encoding_variants.push(quote!(#variant_ident));
+                create_arms.push(quote!(
+                    #encoding_name::#variant_ident => {
+                        #clear_stale
+                        Ok(Self::#variant_ident)
+                    }
+                ));
+                flush_arms.push(quote!(
+                    Self::#variant_ident => Ok(#encoding_name::#variant_ident),
+                ));
+            }
+            VariantFields::Unnamed(field_types) => {
+                let binds: Vec<_> = (0..field_types.len())
+                    .map(|i| format_ident!("field{}", i))
+                    .collect();
+                let substore_indexes: Vec<_> = seq(field_types.len()).collect();
+
+                encoding_variants.push(quote!(
+                    #variant_ident(#(<#field_types as ::orga::state::State>::Encoding),*)
+                ));
+                create_arms.push(quote!(
+                    #encoding_name::#variant_ident(#(#binds),*) => {
+                        #clear_stale
+                        Ok(Self::#variant_ident(
+                            #(::orga::state::State::create(store.sub(&[#substore_indexes]), #binds)?,)*
+                        ))
+                    }
+                ));
+                flush_arms.push(quote!(
+                    Self::#variant_ident(#(#binds),*) => Ok(#encoding_name::#variant_ident(
+                        #(#binds.flush()?,)*
+                    )),
+                ));
+            }
+            VariantFields::Named(field_idents, field_types) => {
+                let substore_indexes: Vec<_> = seq(field_types.len()).collect();
+
+                encoding_variants.push(quote!(
+                    #variant_ident(#(<#field_types as ::orga::state::State>::Encoding),*)
+                ));
+                create_arms.push(quote!(
+                    #encoding_name::#variant_ident(#(#field_idents),*) => {
+                        #clear_stale
+                        Ok(Self::#variant_ident {
+                            #(#field_idents: ::orga::state::State::create(
+                                store.sub(&[#substore_indexes]),
+                                #field_idents,
+                            )?,)*
+                        })
+                    }
+                ));
+                flush_arms.push(quote!(
+                    Self::#variant_ident { #(#field_idents),* } => Ok(#encoding_name::#variant_ident(
+                        #(#field_idents.flush()?,)*
+                    )),
+                ));
+            }
+        }
+    }
+
+    let output = quote! {
+        #[derive(::ed::Encode, ::ed::Decode)]
+        pub enum #encoding_name {
+            #(#encoding_variants,)*
+        }
+
+        impl ::orga::state::State for #name {
+            type Encoding = #encoding_name;
+
+            fn create(
+                store: ::orga::store::Store,
+                data: Self::Encoding,
+            ) -> ::orga::Result<Self> {
+                // Unit-only enums never touch `store`.
+                let _ = &store;
+                match data {
+                    #(#create_arms)*
+                }
+            }
+
+            fn flush(self) -> ::orga::Result<Self::Encoding> {
+                match self {
+                    #(#flush_arms)*
+                }
+            }
+        }
+    };
+
+    output.into()
+}
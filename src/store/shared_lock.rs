@@ -0,0 +1,260 @@
+use crate::state::State;
+use crate::store::Store;
+use crate::Result;
+use std::cell::UnsafeCell;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A single read/write lock shared by several [`Locked`] handles.
+///
+/// When a `State`-derived struct fans out into many substores, wrapping
+/// each one in its own [`super::Shared`] (or [`super::AtomicShared`]) means
+/// paying for a separate lock per field on every operation. `SharedLock`
+/// lets a composite state object register all of its field substores
+/// against one lock instead: a single [`ReadGuard`] (from [`Self::read`])
+/// authorizes reads across every [`Locked`] handle wrapping a value in this
+/// group, and a single [`WriteGuard`] (from [`Self::write`]) authorizes
+/// writes across all of them, so locking happens once per operation rather
+/// than once per field.
+#[derive(Clone)]
+pub struct SharedLock {
+    lock: Arc<RwLock<()>>,
+}
+
+impl SharedLock {
+    /// Constructs a new, empty `SharedLock` group.
+    pub fn new() -> Self {
+        Self {
+            lock: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// Wraps `inner`, registering it under this lock group. The resulting
+    /// [`Locked`] handle is cheap to clone, and only exposes `inner` through
+    /// a guard obtained from this same `SharedLock`.
+    pub fn wrap<T>(&self, inner: T) -> Locked<T> {
+        Locked {
+            lock: self.lock.clone(),
+            inner: Arc::new(UnsafeCell::new(inner)),
+        }
+    }
+
+    /// Takes a shared-read guard token, authorizing reads on every
+    /// [`Locked`] handle registered under this lock.
+    pub fn read(&self) -> ReadGuard {
+        ReadGuard {
+            guard: self.lock.read().unwrap(),
+            lock: Arc::as_ptr(&self.lock),
+        }
+    }
+
+    /// Takes an exclusive-write guard token, authorizing writes on every
+    /// [`Locked`] handle registered under this lock.
+    pub fn write(&self) -> WriteGuard {
+        WriteGuard {
+            guard: self.lock.write().unwrap(),
+            lock: Arc::as_ptr(&self.lock),
+        }
+    }
+}
+
+impl Default for SharedLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A shared-read token taken from a [`SharedLock`], authorizing reads on
+/// every [`Locked`] handle registered under that lock.
+///
+/// Carries the identity of the `SharedLock` it was taken from (the pointer
+/// backing its `Arc<RwLock<()>>`), so [`Locked::read`] can refuse a guard
+/// taken from a different lock instead of silently granting access.
+pub struct ReadGuard<'a> {
+    guard: RwLockReadGuard<'a, ()>,
+    lock: *const RwLock<()>,
+}
+
+/// An exclusive-write token taken from a [`SharedLock`], authorizing reads
+/// and writes on every [`Locked`] handle registered under that lock.
+///
+/// Carries the identity of the `SharedLock` it was taken from, for the same
+/// reason as [`ReadGuard`].
+pub struct WriteGuard<'a> {
+    guard: RwLockWriteGuard<'a, ()>,
+    lock: *const RwLock<()>,
+}
+
+/// A handle to a value registered under a [`SharedLock`] group.
+///
+/// `Locked` is cheap to clone (it just bumps two `Arc` reference counts),
+/// but the wrapped value can only be accessed by presenting a guard token
+/// taken from the same `SharedLock` that produced this handle via
+/// [`SharedLock::wrap`].
+pub struct Locked<T> {
+    lock: Arc<RwLock<()>>,
+    inner: Arc<UnsafeCell<T>>,
+}
+
+// Safety: `inner` is only ever accessed through `read`/`write`, which
+// require a guard proving the owning `SharedLock` is held shared or
+// exclusive, respectively. `Sync` additionally requires `T: Sync`, since
+// `SharedLock::read` can be taken from multiple threads at once, each then
+// handing out a live `&T` through `Locked::read` concurrently.
+unsafe impl<T: Send> Send for Locked<T> {}
+unsafe impl<T: Send + Sync> Sync for Locked<T> {}
+
+impl<T> Clone for Locked<T> {
+    fn clone(&self) -> Self {
+        Self {
+            lock: self.lock.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Locked<T> {
+    /// Borrows the wrapped value, authorized by a [`ReadGuard`] taken from
+    /// the owning [`SharedLock`].
+    ///
+    /// Panics if `guard` was taken from a different `SharedLock` than the
+    /// one this handle was registered under.
+    pub fn read<'a>(&'a self, guard: &'a ReadGuard) -> &'a T {
+        assert_eq!(
+            Arc::as_ptr(&self.lock),
+            guard.lock,
+            "ReadGuard does not belong to this Locked value's SharedLock",
+        );
+        unsafe { &*self.inner.get() }
+    }
+
+    /// Mutably borrows the wrapped value, authorized by a [`WriteGuard`]
+    /// taken from the owning [`SharedLock`].
+    ///
+    /// Panics if `guard` was taken from a different `SharedLock` than the
+    /// one this handle was registered under.
+    pub fn write<'a>(&'a self, guard: &'a mut WriteGuard) -> &'a mut T {
+        assert_eq!(
+            Arc::as_ptr(&self.lock),
+            guard.lock,
+            "WriteGuard does not belong to this Locked value's SharedLock",
+        );
+        unsafe { &mut *self.inner.get() }
+    }
+}
+
+/// Adapter letting `#[derive(State)]` register a field's substore under a
+/// shared lock group instead of giving it its own lock, the way a field
+/// would normally get its own [`super::Shared`]/[`super::AtomicShared`].
+///
+/// A derived struct opts in per-field by declaring a field's type as
+/// `Locked<FieldType>` alongside one field of type `SharedLock` - the
+/// derive then generates a fresh `SharedLock` in `create`, threads it into
+/// [`Self::create_field`] for every `Locked` field, and takes one
+/// [`WriteGuard`] in `flush` to flush all of them instead of locking once
+/// per field.
+impl<T: State> Locked<T> {
+    /// Creates `T` the same way `#[derive(State)]` creates an ordinary
+    /// field, then registers it under `lock`.
+    pub fn create_field(lock: &SharedLock, store: Store, data: T::Encoding) -> Result<Self> {
+        Ok(lock.wrap(T::create(store, data)?))
+    }
+
+    /// Flushes the wrapped field, authorized by a [`WriteGuard`] taken from
+    /// the owning `SharedLock`.
+    ///
+    /// Panics if `guard` was taken from a different `SharedLock`, or if
+    /// another `Locked` handle to the same field is still alive - flushing
+    /// needs to take the field back by value, so (like `Shared`) this
+    /// relies on the invariant that operations on a field never overlap.
+    pub fn flush_field(self, guard: &WriteGuard) -> Result<T::Encoding> {
+        assert_eq!(
+            Arc::as_ptr(&self.lock),
+            guard.lock,
+            "WriteGuard does not belong to this Locked value's SharedLock",
+        );
+        let cell = Arc::try_unwrap(self.inner)
+            .unwrap_or_else(|_| panic!("Locked field flushed while another handle to it is alive"));
+        cell.into_inner().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MapStore;
+
+    struct Counter(u32);
+
+    impl State for Counter {
+        type Encoding = u32;
+
+        fn create(_store: Store, data: Self::Encoding) -> Result<Self> {
+            Ok(Counter(data))
+        }
+
+        fn flush(self) -> Result<Self::Encoding> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn create_field_and_flush_field_roundtrip() {
+        let lock = SharedLock::new();
+        let store = Store::new(MapStore::new());
+
+        let a = Locked::<Counter>::create_field(&lock, store.clone(), 5).unwrap();
+        let b = Locked::<Counter>::create_field(&lock, store, 9).unwrap();
+
+        {
+            let mut guard = lock.write();
+            a.write(&mut guard).0 += 1;
+            b.write(&mut guard).0 += 1;
+        }
+
+        let guard = lock.write();
+        assert_eq!(a.flush_field(&guard).unwrap(), 6);
+        assert_eq!(b.flush_field(&guard).unwrap(), 10);
+    }
+
+    #[test]
+    fn locked_handles_observe_each_others_writes() {
+        let lock = SharedLock::new();
+        let a = lock.wrap(1);
+        let b = lock.wrap("hello".to_string());
+
+        {
+            let mut guard = lock.write();
+            *a.write(&mut guard) += 1;
+            b.write(&mut guard).push_str(", world");
+        }
+
+        let a2 = a.clone();
+        let guard = lock.read();
+        assert_eq!(*a.read(&guard), 2);
+        assert_eq!(*a2.read(&guard), 2);
+        assert_eq!(b.read(&guard), "hello, world");
+    }
+
+    #[test]
+    fn read_guard_authorizes_multiple_handles_at_once() {
+        let lock = SharedLock::new();
+        let a = lock.wrap(vec![1, 2, 3]);
+        let b = lock.wrap(vec![4, 5, 6]);
+
+        let guard = lock.read();
+        assert_eq!(a.read(&guard), &[1, 2, 3]);
+        assert_eq!(b.read(&guard), &[4, 5, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not belong to this Locked value's SharedLock")]
+    fn guard_from_a_different_shared_lock_is_rejected() {
+        let lock_a = SharedLock::new();
+        let lock_b = SharedLock::new();
+
+        let a = lock_a.wrap(0i32);
+        let guard_b = lock_b.read();
+
+        a.read(&guard_b);
+    }
+}
@@ -0,0 +1,35 @@
+use orga::state::State;
+use orga::store::shared_lock::{Locked, SharedLock};
+use orga::store::{MapStore, Store};
+
+#[derive(State)]
+struct Composite {
+    pub lock: SharedLock,
+    pub a: Locked<u32>,
+    pub b: Locked<u32>,
+}
+
+fn store() -> Store {
+    Store::new(MapStore::new())
+}
+
+#[test]
+fn derive_groups_locked_fields_under_one_shared_lock() {
+    let composite = Composite::create(
+        store(),
+        (<u32 as State>::Encoding::from(1), <u32 as State>::Encoding::from(2)),
+    )
+    .unwrap();
+
+    // `a` and `b` were registered under the same `SharedLock` by the
+    // derive, so one guard taken from `composite.lock` authorizes reading
+    // both fields.
+    let guard = composite.lock.read();
+    assert_eq!(*composite.a.read(&guard), 1);
+    assert_eq!(*composite.b.read(&guard), 2);
+    drop(guard);
+
+    let (a_encoding, b_encoding) = composite.flush().unwrap();
+    assert_eq!(u32::from(a_encoding), 1);
+    assert_eq!(u32::from(b_encoding), 2);
+}